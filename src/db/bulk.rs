@@ -0,0 +1,188 @@
+//! Plural state retrieval: fetching every row of a kind in one query instead
+//! of one round-trip per key.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use futures_util::TryStreamExt;
+use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+use ruma::{
+    events::{AnySyncStateEvent, StateEventType},
+    serde::Raw,
+    OwnedUserId, RoomId,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Row,
+};
+
+use crate::{helpers::SqlType, StateStore, SupportedDatabase};
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Retrieves every state event of a given type in a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_state_events(
+        &self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+    ) -> Result<Vec<Raw<AnySyncStateEvent>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::state_events_load_query()
+            .bind(room_id.to_string())
+            .bind(event_type.to_string())
+            .fetch(&*self.db);
+
+        let mut events = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let event: Json<Raw<AnySyncStateEvent>> = row.try_get("state_event")?;
+            events.push(event.0);
+        }
+        Ok(events)
+    }
+
+    /// Retrieves every stored member profile in a room, keyed by user id
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_profiles(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<BTreeMap<OwnedUserId, MinimalRoomMemberEvent>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<MinimalRoomMemberEvent>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::profiles_load_query()
+            .bind(room_id.to_string())
+            .fetch(&*self.db);
+
+        let mut profiles = BTreeMap::new();
+        while let Some(row) = rows.try_next().await? {
+            let user_id: String = row.try_get("user_id")?;
+            let profile: Json<MinimalRoomMemberEvent> = row.try_get("profile")?;
+            profiles.insert(user_id.try_into()?, profile.0);
+        }
+        Ok(profiles)
+    }
+
+    /// Retrieves the ids of every member of a room with a stored membership
+    /// event, stripped or not, whatever its membership
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::user_ids_load_query()
+            .bind(room_id.to_string())
+            .fetch(&*self.db);
+
+        let mut user_ids = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let user_id: String = row.try_get("user_id")?;
+            user_ids.push(user_id.try_into()?);
+        }
+        Ok(user_ids)
+    }
+
+    /// Retrieves the ids of every joined (non-stripped) member of a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_joined_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::joined_user_ids_load_query()
+            .bind(room_id.to_string())
+            .fetch(&*self.db);
+
+        let mut user_ids = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let user_id: String = row.try_get("user_id")?;
+            user_ids.push(user_id.try_into()?);
+        }
+        Ok(user_ids)
+    }
+
+    /// Retrieves the ids of every invited (stripped) member of a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_invited_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::invited_user_ids_load_query()
+            .bind(room_id.to_string())
+            .fetch(&*self.db);
+
+        let mut user_ids = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let user_id: String = row.try_get("user_id")?;
+            user_ids.push(user_id.try_into()?);
+        }
+        Ok(user_ids)
+    }
+
+    /// Retrieves info for every joined room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_room_infos(&self) -> Result<Vec<RoomInfo>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::room_infos_load_query().fetch(&*self.db);
+
+        let mut infos = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let info: Json<RoomInfo> = row.try_get("room_info")?;
+            infos.push(info.0);
+        }
+        Ok(infos)
+    }
+
+    /// Retrieves info for every invited (stripped) room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_stripped_room_infos(&self) -> Result<Vec<RoomInfo>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::stripped_room_infos_load_query().fetch(&*self.db);
+
+        let mut infos = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let info: Json<RoomInfo> = row.try_get("room_info")?;
+            infos.push(info.0);
+        }
+        Ok(infos)
+    }
+}
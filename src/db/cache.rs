@@ -0,0 +1,77 @@
+//! A small bounded read-through cache used to avoid re-hitting the database
+//! for state that's read repeatedly during sync.
+use std::{
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::Mutex,
+};
+
+use lru::LruCache;
+
+/// A bounded, optionally-disabled LRU cache for one kind of query.
+///
+/// Constructing with a capacity of `0` disables the cache entirely: [`get`]
+/// always misses and [`put`]/[`invalidate`] are no-ops, so callers don't need
+/// to special-case the disabled configuration.
+///
+/// [`get`]: QueryCache::get
+/// [`put`]: QueryCache::put
+/// [`invalidate`]: QueryCache::invalidate
+pub(crate) struct QueryCache<K, V> {
+    inner: Option<Mutex<LruCache<K, V>>>,
+}
+
+impl<K: Eq + Hash, V: Clone> QueryCache<K, V> {
+    /// Creates a new cache holding at most `capacity` entries, or a disabled
+    /// no-op cache if `capacity` is `0`.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            inner: NonZeroUsize::new(capacity).map(|cap| Mutex::new(LruCache::new(cap))),
+        }
+    }
+
+    /// Returns a clone of the cached value for `key`, if present.
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        let cache = self.inner.as_ref()?;
+        cache.lock().expect("cache lock poisoned").get(key).cloned()
+    }
+
+    /// Inserts or refreshes the cached value for `key`.
+    pub(crate) fn put(&self, key: K, value: V) {
+        if let Some(cache) = &self.inner {
+            cache.lock().expect("cache lock poisoned").put(key, value);
+        }
+    }
+
+    /// Removes any cached value for `key`, forcing the next [`get`](Self::get) to miss.
+    pub(crate) fn invalidate(&self, key: &K) {
+        if let Some(cache) = &self.inner {
+            cache.lock().expect("cache lock poisoned").pop(key);
+        }
+    }
+
+    /// Removes every cached entry for which `keep` returns `false`.
+    ///
+    /// Used to evict every key scoped to a room (or other compound key)
+    /// when a point [`invalidate`](Self::invalidate) can't express the match,
+    /// e.g. clearing all `(room_id, event_type, state_key)` state-cache
+    /// entries for a single `room_id` after [`StateStore::remove_room`].
+    ///
+    /// [`StateStore::remove_room`]: crate::StateStore::remove_room
+    pub(crate) fn retain(&self, mut keep: impl FnMut(&K) -> bool)
+    where
+        K: Clone,
+    {
+        if let Some(cache) = &self.inner {
+            let mut cache = cache.lock().expect("cache lock poisoned");
+            let stale: Vec<K> = cache
+                .iter()
+                .filter(|(key, _)| !keep(key))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in stale {
+                cache.pop(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Generic key/value storage backing the sync token, saved filters, and the
+//! [`matrix_sdk_base::StateStore`] custom-value API, all of which just need
+//! "remember this blob under this name" rather than a dedicated table.
+use anyhow::Result;
+use sqlx::{database::HasArguments, ColumnIndex, Database, Executor, IntoArguments, Row, Transaction};
+
+use crate::{helpers::SqlType, StateStore, SupportedDatabase};
+
+/// The `kv_store` key the sync token is saved under.
+const SYNC_TOKEN_KEY: &str = "sync_token";
+
+/// Builds the `kv_store` key a named filter's id is saved under.
+fn filter_key(filter_name: &str) -> String {
+    format!("filter:{filter_name}")
+}
+
+/// Builds the `kv_store` key a custom value's arbitrary byte key is saved
+/// under. `kv_store` keys are `TEXT` and caller keys may not be UTF-8, so every
+/// caller key is hex-encoded rather than stored raw, and namespaced so they can't collide with the
+/// sync-token/filter keys above.
+fn custom_value_key(key: &[u8]) -> String {
+    let mut hex = String::with_capacity(key.len() * 2 + "custom:".len());
+    hex.push_str("custom:");
+    for byte in key {
+        hex.push_str(&format!("{byte:02x}"));
+    }
+    hex
+}
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Reads a raw value out of `kv_store` by key.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    async fn get_kv(&self, key: &str) -> Result<Option<Vec<u8>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let row = DB::kv_load_query()
+            .bind(key.to_owned())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(row.try_get("value")?))
+    }
+
+    /// Writes a raw value into `kv_store`, returning the value that was
+    /// previously stored under `key`, if any.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    async fn set_kv(&self, key: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let previous = self.get_kv(key).await?;
+        DB::kv_upsert_query()
+            .bind(key.to_owned())
+            .bind(value)
+            .execute(&*self.db)
+            .await?;
+        Ok(previous)
+    }
+
+    /// Retrieves the sync token saved by the last call to
+    /// [`set_sync_token`](Self::set_sync_token).
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_sync_token(&self) -> Result<Option<String>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let Some(bytes) = self.get_kv(SYNC_TOKEN_KEY).await? else {
+            return Ok(None);
+        };
+        Ok(Some(String::from_utf8(bytes)?))
+    }
+
+    /// Saves the sync token to resume from on the next sync.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_sync_token(&self, sync_token: &str) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.set_kv(SYNC_TOKEN_KEY, sync_token.as_bytes().to_vec())
+            .await?;
+        Ok(())
+    }
+
+    /// Saves the sync token as part of an in-progress transaction, for
+    /// [`sdk_store::save_changes`](super::sdk_store) to call alongside its
+    /// other writes instead of opening a second transaction.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub(crate) async fn set_sync_token_in_txn<'c>(
+        txn: &mut Transaction<'c, DB>,
+        sync_token: &str,
+    ) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'a> &'a mut Transaction<'c, DB>: Executor<'a, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+    {
+        DB::kv_upsert_query()
+            .bind(SYNC_TOKEN_KEY.to_owned())
+            .bind(sync_token.as_bytes().to_vec())
+            .execute(txn)
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves a previously-saved filter id by the name it was saved under.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_filter(&self, filter_name: &str) -> Result<Option<String>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let Some(bytes) = self.get_kv(&filter_key(filter_name)).await? else {
+            return Ok(None);
+        };
+        Ok(Some(String::from_utf8(bytes)?))
+    }
+
+    /// Saves a filter id under a name so it can be looked back up by
+    /// [`get_filter`](Self::get_filter).
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn save_filter(&self, filter_name: &str, filter_id: &str) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.set_kv(&filter_key(filter_name), filter_id.as_bytes().to_vec())
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves a caller-defined custom value by its raw key.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_custom_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.get_kv(&custom_value_key(key)).await
+    }
+
+    /// Stores a caller-defined custom value under a raw key, returning the
+    /// value previously stored there, if any.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_custom_value(&self, key: &[u8], value: Vec<u8>) -> Result<Option<Vec<u8>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Vec<u8>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        self.set_kv(&custom_value_key(key), value).await
+    }
+}
@@ -0,0 +1,79 @@
+//! Schema versioning and migration support.
+use anyhow::Result;
+use sqlx::{database::HasArguments, ColumnIndex, Database, Executor, IntoArguments, Row};
+
+use crate::{helpers::SqlType, StateStore, SupportedDatabase};
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Brings the database schema up to date.
+    ///
+    /// Applies every migration script from [`SupportedDatabase::migrations`]
+    /// whose index is greater than the currently recorded schema version, in
+    /// order, bumping `_schema_version` after each one. Everything runs in a
+    /// single transaction, so a failing migration rolls the whole batch back.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn migrate(&self) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut sqlx::Transaction<'t, DB>: Executor<'c, Database = DB>,
+        i64: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut txn = self.db.begin().await?;
+
+        DB::schema_version_create_table_query()
+            .execute(&mut txn)
+            .await?;
+        DB::schema_version_seed_query().execute(&mut txn).await?;
+
+        let version = DB::schema_version_load_query()
+            .fetch_optional(&mut txn)
+            .await?
+            .map(|row| row.try_get::<i64, _>("version"))
+            .transpose()?
+            .unwrap_or(0);
+
+        let mut version = version;
+        for (idx, script) in DB::migrations().iter().enumerate() {
+            let idx = idx as i64 + 1;
+            if idx <= version {
+                continue;
+            }
+            sqlx::query(script).execute(&mut txn).await?;
+            DB::schema_version_update_query()
+                .bind(idx)
+                .execute(&mut txn)
+                .await?;
+            version = idx;
+        }
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the schema version currently recorded in `_schema_version`,
+    /// or `0` if that table holds no row.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails, including
+    /// when [`migrate`](Self::migrate) has never run and `_schema_version`
+    /// does not exist yet
+    pub async fn schema_version(&self) -> Result<i64>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        i64: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let version = DB::schema_version_load_query()
+            .fetch_optional(&*self.db)
+            .await?
+            .map(|row| row.try_get::<i64, _>("version"))
+            .transpose()?
+            .unwrap_or(0);
+        Ok(version)
+    }
+}
@@ -0,0 +1,15 @@
+//! Query implementations for [`crate::StateStore`], split by concern.
+
+mod bulk;
+pub(crate) mod cache;
+mod kv;
+mod migration;
+mod postgres;
+mod receipt;
+mod room;
+mod sdk_store;
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub use sdk_store::Error;
@@ -0,0 +1,292 @@
+//! [`SupportedDatabase`] implementation for PostgreSQL.
+//!
+//! Mirrors the semantics (and bind order) of the SQLite implementation in
+//! [`crate::db::sqlite`], but stores JSON columns as `jsonb` and drives
+//! upserts through `ON CONFLICT ... DO UPDATE` with `$n` placeholders, so it
+//! can take concurrent writes from multiple server processes.
+use sqlx::postgres::Postgres;
+
+use crate::{Query, SupportedDatabase};
+
+/// SQL run once, in order, to bring a fresh database up to the current
+/// schema. New migrations must only ever be appended to this list, and each
+/// entry must be a single statement — see [`SupportedDatabase::migrations`].
+const MIGRATIONS: &[&str] = &[
+    r"
+    CREATE TABLE IF NOT EXISTS account_data (
+        room_id TEXT NULL,
+        event_type TEXT NOT NULL,
+        account_data JSONB NOT NULL
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS presence (
+        user_id TEXT PRIMARY KEY,
+        presence JSONB NOT NULL
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_membership (
+        room_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        member_event JSONB NOT NULL,
+        displayname TEXT NULL,
+        PRIMARY KEY (room_id, user_id, stripped)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_profile (
+        room_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        profile JSONB NOT NULL,
+        PRIMARY KEY (room_id, user_id)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_state (
+        room_id TEXT NOT NULL,
+        event_type TEXT NOT NULL,
+        state_key TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        state_event JSONB NOT NULL,
+        PRIMARY KEY (room_id, event_type, state_key, stripped)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS receipts (
+        room_id TEXT NOT NULL,
+        event_id TEXT NOT NULL,
+        receipt_type TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        receipt JSONB NOT NULL,
+        PRIMARY KEY (room_id, receipt_type, user_id)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS rooms (
+        room_id TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        room_info JSONB NOT NULL,
+        PRIMARY KEY (room_id, stripped)
+    );
+    ",
+    r"
+    CREATE INDEX IF NOT EXISTS receipts_room_type_event_idx
+        ON receipts (room_id, receipt_type, event_id);
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS kv_store (
+        key TEXT PRIMARY KEY,
+        value BYTEA NOT NULL
+    );
+    ",
+    // account_data's uniqueness can't live in a PRIMARY KEY: PK columns are
+    // implicitly NOT NULL on Postgres, but room_id is NULL for global
+    // account data, and an insert of a NULL PK column raises a not-null
+    // violation. COALESCE(room_id, '') treats every global row as sharing
+    // one key per event_type, which is exactly the upsert target we want.
+    r"
+    CREATE UNIQUE INDEX IF NOT EXISTS account_data_room_event_idx
+        ON account_data (COALESCE(room_id, ''), event_type);
+    ",
+];
+
+/// The statements run to delete a room, one per [`Executor::execute`] call.
+///
+/// [`Executor::execute`]: sqlx::Executor::execute
+const ROOM_REMOVE_STATEMENTS: &[&str] = &[
+    "DELETE FROM room_membership WHERE room_id = $1",
+    "DELETE FROM room_profile WHERE room_id = $1",
+    "DELETE FROM room_state WHERE room_id = $1",
+    "DELETE FROM receipts WHERE room_id = $1",
+    "DELETE FROM rooms WHERE room_id = $1",
+];
+
+impl SupportedDatabase for Postgres {
+    fn room_remove_queries() -> &'static [&'static str] {
+        ROOM_REMOVE_STATEMENTS
+    }
+
+    fn account_data_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO account_data (room_id, event_type, account_data)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (COALESCE(room_id, ''), event_type)
+             DO UPDATE SET account_data = excluded.account_data",
+        )
+    }
+
+    fn account_data_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT account_data FROM account_data
+             WHERE room_id IS NOT DISTINCT FROM $1 AND event_type = $2",
+        )
+    }
+
+    fn presence_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO presence (user_id, presence)
+             VALUES ($1, $2)
+             ON CONFLICT (user_id)
+             DO UPDATE SET presence = excluded.presence",
+        )
+    }
+
+    fn presence_load_query() -> Query<Self> {
+        sqlx::query("SELECT presence FROM presence WHERE user_id = $1")
+    }
+
+    fn member_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_membership (room_id, user_id, stripped, member_event, displayname)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (room_id, user_id, stripped)
+             DO UPDATE SET member_event = excluded.member_event, displayname = excluded.displayname",
+        )
+    }
+
+    fn member_profile_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_profile (room_id, user_id, profile)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (room_id, user_id)
+             DO UPDATE SET profile = excluded.profile",
+        )
+    }
+
+    fn state_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_state (room_id, event_type, state_key, stripped, state_event)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (room_id, event_type, state_key, stripped)
+             DO UPDATE SET state_event = excluded.state_event",
+        )
+    }
+
+    fn state_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT state_event FROM room_state
+             WHERE room_id = $1 AND event_type = $2 AND state_key = $3 AND stripped = FALSE",
+        )
+    }
+
+    fn receipt_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO receipts (room_id, event_id, receipt_type, user_id, receipt)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (room_id, receipt_type, user_id)
+             DO UPDATE SET event_id = excluded.event_id, receipt = excluded.receipt",
+        )
+    }
+
+    fn room_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO rooms (room_id, stripped, room_info)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (room_id, stripped)
+             DO UPDATE SET room_info = excluded.room_info",
+        )
+    }
+
+    fn room_info_load_query() -> Query<Self> {
+        sqlx::query("SELECT room_info FROM rooms WHERE room_id = $1 AND stripped = FALSE")
+    }
+
+    fn state_events_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT state_event FROM room_state
+             WHERE room_id = $1 AND event_type = $2 AND stripped = FALSE",
+        )
+    }
+
+    fn profiles_load_query() -> Query<Self> {
+        sqlx::query("SELECT user_id, profile FROM room_profile WHERE room_id = $1")
+    }
+
+    fn user_ids_load_query() -> Query<Self> {
+        sqlx::query("SELECT DISTINCT user_id FROM room_membership WHERE room_id = $1")
+    }
+
+    fn room_infos_load_query() -> Query<Self> {
+        sqlx::query("SELECT room_info FROM rooms WHERE stripped = FALSE")
+    }
+
+    fn stripped_room_infos_load_query() -> Query<Self> {
+        sqlx::query("SELECT room_info FROM rooms WHERE stripped = TRUE")
+    }
+
+    fn user_receipt_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT event_id, receipt FROM receipts
+             WHERE room_id = $1 AND receipt_type = $2 AND user_id = $3",
+        )
+    }
+
+    fn event_receipt_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT user_id, receipt FROM receipts
+             WHERE room_id = $1 AND receipt_type = $2 AND event_id = $3",
+        )
+    }
+
+    fn profile_load_query() -> Query<Self> {
+        sqlx::query("SELECT profile FROM room_profile WHERE room_id = $1 AND user_id = $2")
+    }
+
+    fn member_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT member_event, stripped FROM room_membership
+             WHERE room_id = $1 AND user_id = $2
+             ORDER BY stripped ASC LIMIT 1",
+        )
+    }
+
+    fn joined_user_ids_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT DISTINCT user_id FROM room_membership WHERE room_id = $1 AND stripped = FALSE",
+        )
+    }
+
+    fn invited_user_ids_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT DISTINCT user_id FROM room_membership WHERE room_id = $1 AND stripped = TRUE",
+        )
+    }
+
+    fn kv_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO kv_store (key, value)
+             VALUES ($1, $2)
+             ON CONFLICT (key)
+             DO UPDATE SET value = excluded.value",
+        )
+    }
+
+    fn kv_load_query() -> Query<Self> {
+        sqlx::query("SELECT value FROM kv_store WHERE key = $1")
+    }
+
+    fn migrations() -> &'static [&'static str] {
+        MIGRATIONS
+    }
+
+    fn schema_version_create_table_query() -> Query<Self> {
+        sqlx::query("CREATE TABLE IF NOT EXISTS _schema_version (version BIGINT NOT NULL)")
+    }
+
+    fn schema_version_seed_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO _schema_version (version)
+             SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM _schema_version)",
+        )
+    }
+
+    fn schema_version_load_query() -> Query<Self> {
+        sqlx::query("SELECT version FROM _schema_version LIMIT 1")
+    }
+
+    fn schema_version_update_query() -> Query<Self> {
+        sqlx::query("UPDATE _schema_version SET version = $1")
+    }
+}
@@ -0,0 +1,79 @@
+//! Read receipt retrieval, complementing [`StateStore::set_receipt`].
+use anyhow::Result;
+use futures_util::TryStreamExt;
+use ruma::{
+    events::receipt::Receipt, receipt::ReceiptType, EventId, OwnedEventId, OwnedUserId, RoomId,
+    UserId,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments, Row,
+};
+
+use crate::{helpers::SqlType, StateStore, SupportedDatabase};
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Retrieves a user's receipt of a given type in a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_user_room_receipt(
+        &self,
+        room_id: &RoomId,
+        receipt_type: &ReceiptType,
+        user_id: &UserId,
+    ) -> Result<Option<(OwnedEventId, Receipt)>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<Receipt>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let row = DB::user_receipt_load_query()
+            .bind(room_id.to_string())
+            .bind(receipt_type.to_string())
+            .bind(user_id.to_string())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        let event_id: String = row.try_get("event_id")?;
+        let receipt: Json<Receipt> = row.try_get("receipt")?;
+        Ok(Some((event_id.try_into()?, receipt.0)))
+    }
+
+    /// Retrieves every user's receipt pointing at a given event
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_event_room_receipts(
+        &self,
+        room_id: &RoomId,
+        receipt_type: &ReceiptType,
+        event_id: &EventId,
+    ) -> Result<Vec<(OwnedUserId, Receipt)>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<Receipt>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let mut rows = DB::event_receipt_load_query()
+            .bind(room_id.to_string())
+            .bind(receipt_type.to_string())
+            .bind(event_id.to_string())
+            .fetch(&*self.db);
+
+        let mut receipts = Vec::new();
+        while let Some(row) = rows.try_next().await? {
+            let user_id: String = row.try_get("user_id")?;
+            let receipt: Json<Receipt> = row.try_get("receipt")?;
+            receipts.push((user_id.try_into()?, receipt.0));
+        }
+        Ok(receipts)
+    }
+}
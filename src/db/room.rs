@@ -1,6 +1,6 @@
 //! Room database code
 use anyhow::Result;
-use matrix_sdk_base::{MinimalRoomMemberEvent, RoomInfo};
+use matrix_sdk_base::{MemberEvent, MinimalRoomMemberEvent, RoomInfo};
 use ruma::{
     events::{
         presence::PresenceEvent,
@@ -20,6 +20,17 @@ use sqlx::{
 
 use crate::{helpers::SqlType, StateStore, SupportedDatabase};
 
+// Most `set_*` methods below are static (they take a bare `Transaction`, not
+// `&self`) so that `sdk_store::save_changes` can batch every write from a
+// sync response into one transaction. The four that back a cache
+// (`set_global_account_data`, `set_presence_event`, `set_room_state`,
+// `set_room_info`) keep an `_in_txn` twin for that internal use, but are
+// `pub(crate)` rather than `pub`: a public caller going around `&self` would
+// have no way to invalidate the cache afterwards, leaving `get_*` free to
+// return a stale hit. The public entry point for each is the `&self` wrapper
+// of the same name further down, which opens its own transaction and updates
+// the cache before returning. The remaining static setters have no backing
+// cache, so there's nothing to go stale and they stay `pub`.
 impl<DB: SupportedDatabase> StateStore<DB> {
     /// Deletes a room from the room store
     ///
@@ -29,20 +40,37 @@ impl<DB: SupportedDatabase> StateStore<DB> {
     where
         for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
         for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
         String: SqlType<DB>,
     {
-        DB::room_remove_query()
-            .bind(room_id.to_string())
-            .execute(&*self.db)
-            .await?;
+        let mut txn = self.db.begin().await?;
+        for statement in DB::room_remove_queries() {
+            sqlx::query(statement)
+                .bind(room_id.to_string())
+                .execute(&mut txn)
+                .await?;
+        }
+        txn.commit().await?;
+
+        self.room_info_cache.invalidate(&room_id.to_owned());
+        self.state_cache
+            .retain(|(cached_room_id, _, _)| cached_room_id != room_id);
+
         Ok(())
     }
 
-    /// Sets global account data for an account data event
+    /// Sets global account data for an account data event as part of an
+    /// in-progress transaction.
+    ///
+    /// Only [`sdk_store::save_changes`](super::sdk_store) and
+    /// [`set_global_account_data`](Self::set_global_account_data) should call
+    /// this directly — it writes straight through to the database without
+    /// touching `account_data_cache`, so any other caller would need to
+    /// invalidate the cache itself or risk a stale `get_account_data_event`.
     ///
     /// # Errors
     /// This function will return an error if the the query fails
-    pub async fn set_global_account_data<'c>(
+    pub(crate) async fn set_global_account_data_in_txn<'c>(
         txn: &mut Transaction<'c, DB>,
         event_type: &GlobalAccountDataEventType,
         event_data: Raw<AnyGlobalAccountDataEvent>,
@@ -64,6 +92,31 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
+    /// Sets global account data for an account data event
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_global_account_data(
+        &self,
+        event_type: &GlobalAccountDataEventType,
+        event_data: Raw<AnyGlobalAccountDataEvent>,
+    ) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Option<String>: SqlType<DB>,
+        Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    {
+        let mut txn = self.db.begin().await?;
+        Self::set_global_account_data_in_txn(&mut txn, event_type, event_data.clone()).await?;
+        txn.commit().await?;
+
+        self.account_data_cache.put(event_type.clone(), event_data);
+        Ok(())
+    }
+
     /// Get global account data for an account data event type
     ///
     /// # Errors
@@ -80,6 +133,10 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
         for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
     {
+        if let Some(cached) = self.account_data_cache.get(&event_type) {
+            return Ok(Some(cached));
+        }
+
         let row = DB::account_data_load_query()
             .bind(None::<String>)
             .bind(event_type.to_string())
@@ -91,14 +148,20 @@ impl<DB: SupportedDatabase> StateStore<DB> {
             return Ok(None);
         };
         let row: Json<Raw<AnyGlobalAccountDataEvent>> = row.try_get("account_data")?;
+        self.account_data_cache.put(event_type, row.0.clone());
         Ok(Some(row.0))
     }
 
-    /// Sets presence for a user
+    /// Sets presence for a user as part of an in-progress transaction.
+    ///
+    /// Only [`sdk_store::save_changes`](super::sdk_store) and
+    /// [`set_presence_event`](Self::set_presence_event) should call this
+    /// directly — see the note on
+    /// [`set_global_account_data_in_txn`](Self::set_global_account_data_in_txn).
     ///
     /// # Errors
     /// This function will return an error if the the query fails
-    pub async fn set_presence_event<'c>(
+    pub(crate) async fn set_presence_event_in_txn<'c>(
         txn: &mut Transaction<'c, DB>,
         user_id: &UserId,
         presence: Raw<PresenceEvent>,
@@ -117,6 +180,26 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
+    /// Sets presence for a user
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_presence_event(&self, user_id: &UserId, presence: Raw<PresenceEvent>) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<Raw<PresenceEvent>>: SqlType<DB>,
+    {
+        let mut txn = self.db.begin().await?;
+        Self::set_presence_event_in_txn(&mut txn, user_id, presence.clone()).await?;
+        txn.commit().await?;
+
+        self.presence_cache.put(user_id.to_owned(), presence);
+        Ok(())
+    }
+
     /// Gets presence for a user
     ///
     /// # Errors
@@ -129,6 +212,10 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Json<Raw<PresenceEvent>>: SqlType<DB>,
         for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
     {
+        if let Some(cached) = self.presence_cache.get(&user_id.to_owned()) {
+            return Ok(Some(cached));
+        }
+
         let row = DB::presence_load_query()
             .bind(user_id.to_string())
             .fetch_optional(&*self.db)
@@ -139,11 +226,16 @@ impl<DB: SupportedDatabase> StateStore<DB> {
             return Ok(None);
         };
         let row: Json<Raw<PresenceEvent>> = row.try_get("presence")?;
+        self.presence_cache.put(user_id.to_owned(), row.0.clone());
         Ok(Some(row.0))
     }
 
     /// Stores room membership info for a user
     ///
+    /// This is a static method with no `self`, so it bypasses every cache on
+    /// [`StateStore`] — there is no membership cache to invalidate today, but
+    /// keep this in mind if one is added later.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_room_membership<'c>(
@@ -176,6 +268,8 @@ impl<DB: SupportedDatabase> StateStore<DB> {
 
     /// Stores stripped room membership info for a user
     ///
+    /// Bypasses `StateStore`'s caches the same way [`set_room_membership`](Self::set_room_membership) does.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_stripped_room_membership<'c>(
@@ -206,6 +300,9 @@ impl<DB: SupportedDatabase> StateStore<DB> {
 
     /// Stores user profile in room
     ///
+    /// No `self`, no cache invalidation — see the note on
+    /// [`set_global_account_data_in_txn`](Self::set_global_account_data_in_txn).
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_room_profile<'c>(
@@ -229,11 +326,16 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
-    /// Stores a state event for a room
+    /// Stores a state event for a room as part of an in-progress transaction.
+    ///
+    /// Only [`sdk_store::save_changes`](super::sdk_store) and
+    /// [`set_room_state`](Self::set_room_state) should call this directly —
+    /// see the note on
+    /// [`set_global_account_data_in_txn`](Self::set_global_account_data_in_txn).
     ///
     /// # Errors
     /// This function will return an error if the the query fails
-    pub async fn set_room_state<'c>(
+    pub(crate) async fn set_room_state_in_txn<'c>(
         txn: &mut Transaction<'c, DB>,
         room_id: &RoomId,
         event_type: &StateEventType,
@@ -258,8 +360,41 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
+    /// Stores a state event for a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_room_state(
+        &self,
+        room_id: &RoomId,
+        event_type: &StateEventType,
+        state_key: &str,
+        state: Raw<AnySyncStateEvent>,
+    ) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+        bool: SqlType<DB>,
+    {
+        let mut txn = self.db.begin().await?;
+        Self::set_room_state_in_txn(&mut txn, room_id, event_type, state_key, state.clone())
+            .await?;
+        txn.commit().await?;
+
+        let cache_key = (room_id.to_owned(), event_type.clone(), state_key.to_owned());
+        self.state_cache.put(cache_key, state);
+        Ok(())
+    }
+
     /// Stores a stripped state event for a room
     ///
+    /// `state_cache` only ever holds non-stripped state (`get_state_event`
+    /// filters on `stripped = FALSE`), so there is no cached entry for this
+    /// method to invalidate.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_stripped_room_state<'c>(
@@ -289,6 +424,10 @@ impl<DB: SupportedDatabase> StateStore<DB> {
 
     /// Stores account data for a room
     ///
+    /// `account_data_cache` only ever holds global (room-less) account data
+    /// (`get_room_account_data_event` doesn't consult it at all), so there is
+    /// no cached entry for this method to invalidate.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_room_account_data<'c>(
@@ -314,11 +453,16 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
-    /// Stores info for a room
+    /// Stores info for a room as part of an in-progress transaction.
+    ///
+    /// Only [`sdk_store::save_changes`](super::sdk_store) and
+    /// [`set_room_info`](Self::set_room_info) should call this directly —
+    /// see the note on
+    /// [`set_global_account_data_in_txn`](Self::set_global_account_data_in_txn).
     ///
     /// # Errors
     /// This function will return an error if the the query fails
-    pub async fn set_room_info<'c>(
+    pub(crate) async fn set_room_info_in_txn<'c>(
         txn: &mut Transaction<'c, DB>,
         room_id: &RoomId,
         room_info: RoomInfo,
@@ -339,8 +483,33 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Ok(())
     }
 
+    /// Stores info for a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn set_room_info(&self, room_id: &RoomId, room_info: RoomInfo) -> Result<()>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        bool: SqlType<DB>,
+    {
+        let mut txn = self.db.begin().await?;
+        Self::set_room_info_in_txn(&mut txn, room_id, room_info.clone()).await?;
+        txn.commit().await?;
+
+        self.room_info_cache.put(room_id.to_owned(), room_info);
+        Ok(())
+    }
+
     /// Stores stripped info for a room
     ///
+    /// `room_info_cache` only ever holds non-stripped room info
+    /// (`get_room_info` doesn't consult it for stripped rooms), so there is
+    /// no cached entry for this method to invalidate.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_stripped_room_info<'c>(
@@ -366,6 +535,11 @@ impl<DB: SupportedDatabase> StateStore<DB> {
 
     /// Stores receipt for an event
     ///
+    /// No `self`, no cache invalidation — there is no receipt cache today,
+    /// but see the note on
+    /// [`set_global_account_data_in_txn`](Self::set_global_account_data_in_txn)
+    /// if one is added later.
+    ///
     /// # Errors
     /// This function will return an error if the the query fails
     pub async fn set_receipt<'c>(
@@ -410,6 +584,11 @@ impl<DB: SupportedDatabase> StateStore<DB> {
         Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
         for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
     {
+        let cache_key = (room_id.to_owned(), event_type.clone(), state_key.to_owned());
+        if let Some(cached) = self.state_cache.get(&cache_key) {
+            return Ok(Some(cached));
+        }
+
         let row = DB::state_load_query()
             .bind(room_id.to_string())
             .bind(event_type.to_string())
@@ -422,6 +601,139 @@ impl<DB: SupportedDatabase> StateStore<DB> {
             return Ok(None);
         };
         let row: Json<Raw<AnySyncStateEvent>> = row.try_get("state_event")?;
+        self.state_cache.put(cache_key, row.0.clone());
         Ok(Some(row.0))
     }
+
+    /// Retrieves info for a joined room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_room_info(&self, room_id: &RoomId) -> Result<Option<RoomInfo>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<RoomInfo>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        if let Some(cached) = self.room_info_cache.get(&room_id.to_owned()) {
+            return Ok(Some(cached));
+        }
+
+        let row = DB::room_info_load_query()
+            .bind(room_id.to_string())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        let row: Json<RoomInfo> = row.try_get("room_info")?;
+        self.room_info_cache.put(room_id.to_owned(), row.0.clone());
+        Ok(Some(row.0))
+    }
+
+    /// Retrieves account data for a room and account data event type
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_room_account_data_event(
+        &self,
+        room_id: &RoomId,
+        event_type: RoomAccountDataEventType,
+    ) -> Result<Option<Raw<AnyRoomAccountDataEvent>>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Option<String>: SqlType<DB>,
+        Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let row = DB::account_data_load_query()
+            .bind(Some(room_id.to_string()))
+            .bind(event_type.to_string())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        let row: Json<Raw<AnyRoomAccountDataEvent>> = row.try_get("account_data")?;
+        Ok(Some(row.0))
+    }
+
+    /// Retrieves a single stored member profile in a room
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_profile(
+        &self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> Result<Option<MinimalRoomMemberEvent>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        Json<MinimalRoomMemberEvent>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let row = DB::profile_load_query()
+            .bind(room_id.to_string())
+            .bind(user_id.to_string())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+        let profile: Json<MinimalRoomMemberEvent> = row.try_get("profile")?;
+        Ok(Some(profile.0))
+    }
+
+    /// Retrieves a single room member's membership event, whether joined/left
+    /// or (if the room is only known from an invite) stripped.
+    ///
+    /// # Errors
+    /// This function will return an error if the the query fails
+    pub async fn get_member_event(
+        &self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> Result<Option<MemberEvent>>
+    where
+        for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+        for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+        String: SqlType<DB>,
+        bool: SqlType<DB>,
+        Json<SyncRoomMemberEvent>: SqlType<DB>,
+        Json<StrippedRoomMemberEvent>: SqlType<DB>,
+        for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+    {
+        let row = DB::member_load_query()
+            .bind(room_id.to_string())
+            .bind(user_id.to_string())
+            .fetch_optional(&*self.db)
+            .await?;
+        let row = if let Some(row) = row {
+            row
+        } else {
+            return Ok(None);
+        };
+
+        let stripped: bool = row.try_get("stripped")?;
+        let member_event = if stripped {
+            let event: Json<StrippedRoomMemberEvent> = row.try_get("member_event")?;
+            MemberEvent::Stripped(event.0)
+        } else {
+            let event: Json<SyncRoomMemberEvent> = row.try_get("member_event")?;
+            MemberEvent::Sync(event.0)
+        };
+        Ok(Some(member_event))
+    }
 }
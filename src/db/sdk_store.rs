@@ -0,0 +1,392 @@
+//! [`matrix_sdk_base::StateStore`] implementation, wiring this crate's SQL
+//! loaders into the SDK's sync loop so a [`StateStore`] can be handed
+//! straight to a `matrix_sdk::Client` instead of being driven by hand.
+//!
+//! Media content (`add_media_content`/`get_media_content`/
+//! `remove_media_content`/`remove_media_content_for_uri`) is deliberately
+//! left as a documented no-op below: media blobs have a different
+//! size/retention profile than the relational state this crate otherwise
+//! persists (large binary payloads, garbage-collected by MXC URI rather than
+//! room), and bolting that onto `kv_store` would need its own storage and GC
+//! design rather than reusing the schema built for sync state. Until that
+//! design exists, callers relying on this store for the SDK media cache will
+//! always get a cache miss rather than a silently wrong answer.
+use async_trait::async_trait;
+use matrix_sdk_base::{
+    media::MediaRequest, MemberEvent, MinimalRoomMemberEvent, RoomInfo, StateChanges,
+    StateStore as SdkStateStore, StoreError,
+};
+use ruma::{
+    events::{
+        presence::PresenceEvent,
+        receipt::Receipt,
+        room::member::{StrippedRoomMemberEvent, SyncRoomMemberEvent},
+        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncStateEvent, GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType,
+    },
+    receipt::ReceiptType,
+    serde::Raw,
+    EventId, OwnedEventId, OwnedUserId, RoomId, UserId,
+};
+use sqlx::{
+    database::HasArguments, types::Json, ColumnIndex, Database, Executor, IntoArguments,
+    Transaction,
+};
+use thiserror::Error;
+
+use crate::{helpers::SqlType, StateStore, SupportedDatabase};
+
+/// Errors that can occur driving a [`StateStore`] through the SDK's
+/// [`matrix_sdk_base::StateStore`] trait.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A query failed, or a value failed to (de)serialize.
+    #[error(transparent)]
+    Sql(#[from] anyhow::Error),
+}
+
+impl From<Error> for StoreError {
+    fn from(err: Error) -> Self {
+        StoreError::backend(err)
+    }
+}
+
+#[async_trait]
+impl<DB: SupportedDatabase> SdkStateStore for StateStore<DB>
+where
+    for<'a> <DB as HasArguments<'a>>::Arguments: IntoArguments<'a, DB>,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'t, 'c> &'c mut Transaction<'t, DB>: Executor<'c, Database = DB>,
+    String: SqlType<DB>,
+    Option<String>: SqlType<DB>,
+    bool: SqlType<DB>,
+    Vec<u8>: SqlType<DB>,
+    Json<Raw<AnyGlobalAccountDataEvent>>: SqlType<DB>,
+    Json<Raw<PresenceEvent>>: SqlType<DB>,
+    Json<Raw<AnySyncStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyStrippedStateEvent>>: SqlType<DB>,
+    Json<Raw<AnyRoomAccountDataEvent>>: SqlType<DB>,
+    Json<MinimalRoomMemberEvent>: SqlType<DB>,
+    Json<SyncRoomMemberEvent>: SqlType<DB>,
+    Json<StrippedRoomMemberEvent>: SqlType<DB>,
+    Json<RoomInfo>: SqlType<DB>,
+    Json<Receipt>: SqlType<DB>,
+    for<'a> &'a str: ColumnIndex<<DB as Database>::Row>,
+{
+    type Error = Error;
+
+    /// Persists a whole sync response's worth of changes in one transaction.
+    async fn save_changes(&self, changes: &StateChanges) -> Result<(), Self::Error> {
+        let mut txn = self.db.begin().await.map_err(anyhow::Error::from)?;
+
+        for (event_type, event) in &changes.account_data {
+            Self::set_global_account_data_in_txn(&mut txn, event_type, event.clone())
+                .await
+                .map_err(Error::Sql)?;
+        }
+
+        for (user_id, event) in &changes.presence {
+            Self::set_presence_event_in_txn(&mut txn, user_id, event.clone())
+                .await
+                .map_err(Error::Sql)?;
+        }
+
+        for (room_id, room_state) in &changes.state {
+            for (event_type, events) in room_state {
+                for (state_key, event) in events {
+                    Self::set_room_state_in_txn(&mut txn, room_id, event_type, state_key, event.clone())
+                        .await
+                        .map_err(Error::Sql)?;
+                }
+            }
+        }
+
+        for (room_id, room_state) in &changes.stripped_state {
+            for (event_type, events) in room_state {
+                for (state_key, event) in events {
+                    Self::set_stripped_room_state(&mut txn, room_id, event_type, state_key, event.clone())
+                        .await
+                        .map_err(Error::Sql)?;
+                }
+            }
+        }
+
+        for (room_id, members) in &changes.members {
+            for (user_id, member_event) in members {
+                Self::set_room_membership(&mut txn, room_id, user_id, member_event.clone())
+                    .await
+                    .map_err(Error::Sql)?;
+            }
+        }
+
+        for (room_id, members) in &changes.stripped_members {
+            for (user_id, member_event) in members {
+                Self::set_stripped_room_membership(&mut txn, room_id, user_id, member_event.clone())
+                    .await
+                    .map_err(Error::Sql)?;
+            }
+        }
+
+        for (room_id, members) in &changes.profiles {
+            for (user_id, profile) in members {
+                Self::set_room_profile(&mut txn, room_id, user_id, profile.clone())
+                    .await
+                    .map_err(Error::Sql)?;
+            }
+        }
+
+        for (room_id, room_account_data) in &changes.room_account_data {
+            for (event_type, event) in room_account_data {
+                Self::set_room_account_data(&mut txn, room_id, event_type, event.clone())
+                    .await
+                    .map_err(Error::Sql)?;
+            }
+        }
+
+        for (room_id, room_info) in &changes.room_infos {
+            Self::set_room_info_in_txn(&mut txn, room_id, room_info.clone())
+                .await
+                .map_err(Error::Sql)?;
+        }
+
+        for (room_id, receipt_content) in &changes.receipts {
+            for (event_id, receipts_by_type) in &receipt_content.0 {
+                for (receipt_type, receipts_by_user) in receipts_by_type {
+                    for (user_id, receipt) in receipts_by_user {
+                        Self::set_receipt(&mut txn, room_id, event_id, receipt_type, user_id, receipt.clone())
+                            .await
+                            .map_err(Error::Sql)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(sync_token) = &changes.sync_token {
+            Self::set_sync_token_in_txn(&mut txn, sync_token)
+                .await
+                .map_err(Error::Sql)?;
+        }
+
+        txn.commit().await.map_err(anyhow::Error::from)?;
+
+        // The transaction is durable now, so the caches can be updated to
+        // match without risking a reader observing a value that a rollback
+        // would have undone.
+        for (event_type, event) in &changes.account_data {
+            self.account_data_cache.put(event_type.clone(), event.clone());
+        }
+        for (user_id, event) in &changes.presence {
+            self.presence_cache.put(user_id.clone(), event.clone());
+        }
+        for (room_id, room_state) in &changes.state {
+            for (event_type, events) in room_state {
+                for (state_key, event) in events {
+                    self.state_cache.put(
+                        (room_id.clone(), event_type.clone(), state_key.clone()),
+                        event.clone(),
+                    );
+                }
+            }
+        }
+        for (room_id, room_info) in &changes.room_infos {
+            self.room_info_cache.put(room_id.clone(), room_info.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn get_presence_event(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Option<Raw<PresenceEvent>>, Self::Error> {
+        StateStore::get_presence_event(self, user_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_state_event(
+        &self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+        state_key: &str,
+    ) -> Result<Option<Raw<AnySyncStateEvent>>, Self::Error> {
+        StateStore::get_state_event(self, room_id, event_type, state_key)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_account_data_event(
+        &self,
+        event_type: GlobalAccountDataEventType,
+    ) -> Result<Option<Raw<AnyGlobalAccountDataEvent>>, Self::Error> {
+        StateStore::get_account_data_event(self, event_type)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn remove_room(&self, room_id: &RoomId) -> Result<(), Self::Error> {
+        StateStore::remove_room(self, room_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_sync_token(&self) -> Result<Option<String>, Self::Error> {
+        StateStore::get_sync_token(self).await.map_err(Error::Sql)
+    }
+
+    async fn save_filter(&self, filter_name: &str, filter_id: &str) -> Result<(), Self::Error> {
+        StateStore::save_filter(self, filter_name, filter_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_filter(&self, filter_name: &str) -> Result<Option<String>, Self::Error> {
+        StateStore::get_filter(self, filter_name)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_state_events(
+        &self,
+        room_id: &RoomId,
+        event_type: StateEventType,
+    ) -> Result<Vec<Raw<AnySyncStateEvent>>, Self::Error> {
+        StateStore::get_state_events(self, room_id, event_type)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_profile(
+        &self,
+        room_id: &RoomId,
+        user_id: &UserId,
+    ) -> Result<Option<MinimalRoomMemberEvent>, Self::Error> {
+        StateStore::get_profile(self, room_id, user_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_member_event(
+        &self,
+        room_id: &RoomId,
+        state_key: &UserId,
+    ) -> Result<Option<MemberEvent>, Self::Error> {
+        StateStore::get_member_event(self, room_id, state_key)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_user_ids(&self, room_id: &RoomId) -> Result<Vec<OwnedUserId>, Self::Error> {
+        StateStore::get_user_ids(self, room_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_joined_user_ids(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<Vec<OwnedUserId>, Self::Error> {
+        StateStore::get_joined_user_ids(self, room_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_invited_user_ids(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<Vec<OwnedUserId>, Self::Error> {
+        StateStore::get_invited_user_ids(self, room_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_room_infos(&self) -> Result<Vec<RoomInfo>, Self::Error> {
+        StateStore::get_room_infos(self).await.map_err(Error::Sql)
+    }
+
+    async fn get_stripped_room_infos(&self) -> Result<Vec<RoomInfo>, Self::Error> {
+        StateStore::get_stripped_room_infos(self)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_room_account_data_event(
+        &self,
+        room_id: &RoomId,
+        event_type: RoomAccountDataEventType,
+    ) -> Result<Option<Raw<AnyRoomAccountDataEvent>>, Self::Error> {
+        StateStore::get_room_account_data_event(self, room_id, event_type)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_user_room_receipt(
+        &self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        user_id: &UserId,
+    ) -> Result<Option<(OwnedEventId, Receipt)>, Self::Error> {
+        StateStore::get_user_room_receipt(self, room_id, &receipt_type, user_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_event_room_receipts(
+        &self,
+        room_id: &RoomId,
+        receipt_type: ReceiptType,
+        event_id: &EventId,
+    ) -> Result<Vec<(OwnedUserId, Receipt)>, Self::Error> {
+        StateStore::get_event_room_receipts(self, room_id, &receipt_type, event_id)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn get_custom_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        StateStore::get_custom_value(self, key)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    async fn set_custom_value(
+        &self,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        StateStore::set_custom_value(self, key, value)
+            .await
+            .map_err(Error::Sql)
+    }
+
+    /// A no-op that discards the content — see the module-level doc comment
+    /// for why media content isn't persisted by this store.
+    async fn add_media_content(
+        &self,
+        _request: &MediaRequest,
+        _content: Vec<u8>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Always misses — see the module-level doc comment for why media
+    /// content isn't persisted by this store.
+    async fn get_media_content(
+        &self,
+        _request: &MediaRequest,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// A no-op — there is nothing to remove, see the module-level doc comment.
+    async fn remove_media_content(&self, _request: &MediaRequest) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// A no-op — there is nothing to remove, see the module-level doc comment.
+    async fn remove_media_content_for_uri(
+        &self,
+        _uri: &ruma::MxcUri,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
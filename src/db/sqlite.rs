@@ -0,0 +1,294 @@
+//! [`SupportedDatabase`] implementation for SQLite.
+use sqlx::sqlite::Sqlite;
+
+use crate::{Query, SupportedDatabase};
+
+/// SQL run once, in order, to bring a fresh database up to the current
+/// schema. New migrations must only ever be appended to this list, and each
+/// entry must be a single statement — see [`SupportedDatabase::migrations`].
+const MIGRATIONS: &[&str] = &[
+    r"
+    CREATE TABLE IF NOT EXISTS account_data (
+        room_id TEXT NULL,
+        event_type TEXT NOT NULL,
+        account_data JSON NOT NULL
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS presence (
+        user_id TEXT PRIMARY KEY,
+        presence JSON NOT NULL
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_membership (
+        room_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        member_event JSON NOT NULL,
+        displayname TEXT NULL,
+        PRIMARY KEY (room_id, user_id, stripped)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_profile (
+        room_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        profile JSON NOT NULL,
+        PRIMARY KEY (room_id, user_id)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS room_state (
+        room_id TEXT NOT NULL,
+        event_type TEXT NOT NULL,
+        state_key TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        state_event JSON NOT NULL,
+        PRIMARY KEY (room_id, event_type, state_key, stripped)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS receipts (
+        room_id TEXT NOT NULL,
+        event_id TEXT NOT NULL,
+        receipt_type TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        receipt JSON NOT NULL,
+        PRIMARY KEY (room_id, receipt_type, user_id)
+    );
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS rooms (
+        room_id TEXT NOT NULL,
+        stripped BOOLEAN NOT NULL,
+        room_info JSON NOT NULL,
+        PRIMARY KEY (room_id, stripped)
+    );
+    ",
+    r"
+    CREATE INDEX IF NOT EXISTS receipts_room_type_event_idx
+        ON receipts (room_id, receipt_type, event_id);
+    ",
+    r"
+    CREATE TABLE IF NOT EXISTS kv_store (
+        key TEXT PRIMARY KEY,
+        value BLOB NOT NULL
+    );
+    ",
+    // SQLite allows NULLs in a PRIMARY KEY and treats every NULL as
+    // distinct, so a PK on (room_id, event_type) would never deduplicate
+    // global (room_id IS NULL) account data and ON CONFLICT (room_id,
+    // event_type) would never fire for it — repeated
+    // set_global_account_data calls would just insert more rows.
+    // COALESCE(room_id, '') gives every global row the same key per
+    // event_type instead, so the unique index (and the upsert target
+    // below) actually enforces one row per (room, event_type).
+    r"
+    CREATE UNIQUE INDEX IF NOT EXISTS account_data_room_event_idx
+        ON account_data (COALESCE(room_id, ''), event_type);
+    ",
+];
+
+/// The statements run to delete a room, one per [`Executor::execute`] call.
+///
+/// [`Executor::execute`]: sqlx::Executor::execute
+const ROOM_REMOVE_STATEMENTS: &[&str] = &[
+    "DELETE FROM room_membership WHERE room_id = ?",
+    "DELETE FROM room_profile WHERE room_id = ?",
+    "DELETE FROM room_state WHERE room_id = ?",
+    "DELETE FROM receipts WHERE room_id = ?",
+    "DELETE FROM rooms WHERE room_id = ?",
+];
+
+impl SupportedDatabase for Sqlite {
+    fn room_remove_queries() -> &'static [&'static str] {
+        ROOM_REMOVE_STATEMENTS
+    }
+
+    fn account_data_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO account_data (room_id, event_type, account_data)
+             VALUES (?, ?, ?)
+             ON CONFLICT (COALESCE(room_id, ''), event_type)
+             DO UPDATE SET account_data = excluded.account_data",
+        )
+    }
+
+    fn account_data_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT account_data FROM account_data
+             WHERE room_id IS ? AND event_type = ?",
+        )
+    }
+
+    fn presence_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO presence (user_id, presence)
+             VALUES (?, ?)
+             ON CONFLICT (user_id)
+             DO UPDATE SET presence = excluded.presence",
+        )
+    }
+
+    fn presence_load_query() -> Query<Self> {
+        sqlx::query("SELECT presence FROM presence WHERE user_id = ?")
+    }
+
+    fn member_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_membership (room_id, user_id, stripped, member_event, displayname)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (room_id, user_id, stripped)
+             DO UPDATE SET member_event = excluded.member_event, displayname = excluded.displayname",
+        )
+    }
+
+    fn member_profile_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_profile (room_id, user_id, profile)
+             VALUES (?, ?, ?)
+             ON CONFLICT (room_id, user_id)
+             DO UPDATE SET profile = excluded.profile",
+        )
+    }
+
+    fn state_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO room_state (room_id, event_type, state_key, stripped, state_event)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (room_id, event_type, state_key, stripped)
+             DO UPDATE SET state_event = excluded.state_event",
+        )
+    }
+
+    fn state_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT state_event FROM room_state
+             WHERE room_id = ? AND event_type = ? AND state_key = ? AND stripped = FALSE",
+        )
+    }
+
+    fn receipt_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO receipts (room_id, event_id, receipt_type, user_id, receipt)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (room_id, receipt_type, user_id)
+             DO UPDATE SET event_id = excluded.event_id, receipt = excluded.receipt",
+        )
+    }
+
+    fn room_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO rooms (room_id, stripped, room_info)
+             VALUES (?, ?, ?)
+             ON CONFLICT (room_id, stripped)
+             DO UPDATE SET room_info = excluded.room_info",
+        )
+    }
+
+    fn room_info_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT room_info FROM rooms WHERE room_id = ? AND stripped = FALSE",
+        )
+    }
+
+    fn state_events_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT state_event FROM room_state
+             WHERE room_id = ? AND event_type = ? AND stripped = FALSE",
+        )
+    }
+
+    fn profiles_load_query() -> Query<Self> {
+        sqlx::query("SELECT user_id, profile FROM room_profile WHERE room_id = ?")
+    }
+
+    fn user_ids_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT DISTINCT user_id FROM room_membership WHERE room_id = ?",
+        )
+    }
+
+    fn room_infos_load_query() -> Query<Self> {
+        sqlx::query("SELECT room_info FROM rooms WHERE stripped = FALSE")
+    }
+
+    fn stripped_room_infos_load_query() -> Query<Self> {
+        sqlx::query("SELECT room_info FROM rooms WHERE stripped = TRUE")
+    }
+
+    fn user_receipt_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT event_id, receipt FROM receipts
+             WHERE room_id = ? AND receipt_type = ? AND user_id = ?",
+        )
+    }
+
+    fn event_receipt_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT user_id, receipt FROM receipts
+             WHERE room_id = ? AND receipt_type = ? AND event_id = ?",
+        )
+    }
+
+    fn profile_load_query() -> Query<Self> {
+        sqlx::query("SELECT profile FROM room_profile WHERE room_id = ? AND user_id = ?")
+    }
+
+    fn member_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT member_event, stripped FROM room_membership
+             WHERE room_id = ? AND user_id = ?
+             ORDER BY stripped ASC LIMIT 1",
+        )
+    }
+
+    fn joined_user_ids_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT DISTINCT user_id FROM room_membership WHERE room_id = ? AND stripped = FALSE",
+        )
+    }
+
+    fn invited_user_ids_load_query() -> Query<Self> {
+        sqlx::query(
+            "SELECT DISTINCT user_id FROM room_membership WHERE room_id = ? AND stripped = TRUE",
+        )
+    }
+
+    fn kv_upsert_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO kv_store (key, value)
+             VALUES (?, ?)
+             ON CONFLICT (key)
+             DO UPDATE SET value = excluded.value",
+        )
+    }
+
+    fn kv_load_query() -> Query<Self> {
+        sqlx::query("SELECT value FROM kv_store WHERE key = ?")
+    }
+
+    fn migrations() -> &'static [&'static str] {
+        MIGRATIONS
+    }
+
+    fn schema_version_create_table_query() -> Query<Self> {
+        sqlx::query("CREATE TABLE IF NOT EXISTS _schema_version (version INTEGER NOT NULL)")
+    }
+
+    fn schema_version_seed_query() -> Query<Self> {
+        sqlx::query(
+            "INSERT INTO _schema_version (version)
+             SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM _schema_version)",
+        )
+    }
+
+    fn schema_version_load_query() -> Query<Self> {
+        sqlx::query("SELECT version FROM _schema_version LIMIT 1")
+    }
+
+    fn schema_version_update_query() -> Query<Self> {
+        sqlx::query("UPDATE _schema_version SET version = ?")
+    }
+}
@@ -0,0 +1,21 @@
+//! Small helper traits used to bound generic code over [`sqlx`]'s per-database
+//! type system.
+use sqlx::Database;
+
+/// Marks a Rust type as encodable/decodable for a given [`sqlx::Database`] backend.
+///
+/// This is shorthand for the `sqlx::Encode<'_, DB> + sqlx::Decode<'_, DB> + sqlx::Type<DB>`
+/// bound that otherwise has to be repeated (with lifetimes) on every query
+/// helper in [`crate::db`].
+pub trait SqlType<DB>: for<'a> sqlx::Encode<'a, DB> + for<'a> sqlx::Decode<'a, DB> + sqlx::Type<DB>
+where
+    DB: Database,
+{
+}
+
+impl<DB, T> SqlType<DB> for T
+where
+    DB: Database,
+    T: for<'a> sqlx::Encode<'a, DB> + for<'a> sqlx::Decode<'a, DB> + sqlx::Type<DB>,
+{
+}
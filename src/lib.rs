@@ -0,0 +1,160 @@
+//! A SQL-backed state store for the Matrix Rust SDK.
+//!
+//! [`StateStore`] persists room state, account data, presence and receipts
+//! via [`sqlx`], and is generic over any [`SupportedDatabase`] backend so the
+//! same code drives SQLite and PostgreSQL.
+
+mod db;
+mod helpers;
+
+pub use db::Error;
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use matrix_sdk_base::RoomInfo;
+use ruma::{
+    events::{
+        presence::PresenceEvent, AnyGlobalAccountDataEvent, GlobalAccountDataEventType,
+        AnySyncStateEvent, StateEventType,
+    },
+    serde::Raw,
+    OwnedRoomId, OwnedUserId,
+};
+use sqlx::{database::HasArguments, migrate::MigrateDatabase, Database, Pool};
+
+use crate::{db::cache::QueryCache, helpers::SqlType};
+
+/// An unbound query for a [`SupportedDatabase`], ready for the caller to bind
+/// parameters onto.
+pub type Query<DB> = sqlx::query::Query<'static, DB, <DB as HasArguments<'static>>::Arguments>;
+
+/// A suggested per-cache capacity to pass to
+/// [`StateStore::with_cache_capacity`]. [`StateStore::new`] does not use it:
+/// it disables caching.
+pub const DEFAULT_CACHE_CAPACITY: usize = 1000;
+
+type StateCacheKey = (OwnedRoomId, StateEventType, String);
+type AccountDataCacheKey = GlobalAccountDataEventType;
+
+/// A SQL-backed implementation of room/account state storage.
+///
+/// Generic over the concrete [`sqlx::Database`] backend via
+/// [`SupportedDatabase`]; construct one with [`StateStore::new`] from an
+/// already-configured connection pool. By default every read goes straight
+/// to the database; call [`StateStore::with_cache_capacity`] to put a bounded
+/// LRU cache in front of the `get_*` loaders.
+pub struct StateStore<DB: SupportedDatabase> {
+    pub(crate) db: Arc<Pool<DB>>,
+    pub(crate) state_cache: QueryCache<StateCacheKey, Raw<AnySyncStateEvent>>,
+    pub(crate) presence_cache: QueryCache<OwnedUserId, Raw<PresenceEvent>>,
+    pub(crate) account_data_cache: QueryCache<AccountDataCacheKey, Raw<AnyGlobalAccountDataEvent>>,
+    pub(crate) room_info_cache: QueryCache<OwnedRoomId, RoomInfo>,
+}
+
+impl<DB: SupportedDatabase> StateStore<DB> {
+    /// Creates a new [`StateStore`] backed by the given connection pool, with
+    /// read-through caching disabled.
+    pub fn new(db: Arc<Pool<DB>>) -> Self {
+        Self::with_cache_capacity(db, 0)
+    }
+
+    /// Creates a new [`StateStore`] whose `get_state_event`, `get_presence_event`,
+    /// `get_account_data_event` and room info loaders are backed by a
+    /// per-kind LRU cache holding up to `capacity` entries each. A `capacity`
+    /// of `0` disables caching.
+    pub fn with_cache_capacity(db: Arc<Pool<DB>>, capacity: usize) -> Self {
+        Self {
+            db,
+            state_cache: QueryCache::new(capacity),
+            presence_cache: QueryCache::new(capacity),
+            account_data_cache: QueryCache::new(capacity),
+            room_info_cache: QueryCache::new(capacity),
+        }
+    }
+}
+
+/// A `sqlx` [`Database`] backend with all the queries a [`StateStore`] needs.
+///
+/// Implement this trait to plug a new SQL dialect into the store; every
+/// method returns an unbound [`Query`] that callers bind parameters onto
+/// before executing, keeping the dialect-specific SQL (and its bind order)
+/// isolated to the impl.
+pub trait SupportedDatabase: Database + MigrateDatabase {
+    /// The statements run to delete a room's membership, profiles, state,
+    /// receipts and room info. Room-scoped account data is not deleted.
+    ///
+    /// Each entry has exactly one `room_id` placeholder and must be executed
+    /// as its own `Executor::execute` call — Postgres (and `sqlx` generally,
+    /// once bind parameters are involved) rejects more than one command per
+    /// prepared statement, so these can't be concatenated into one query.
+    fn room_remove_queries() -> &'static [&'static str];
+    /// Upserts global or room account data, keyed by an optional room id.
+    fn account_data_upsert_query() -> Query<Self>;
+    /// Loads global or room account data, keyed by an optional room id.
+    fn account_data_load_query() -> Query<Self>;
+    /// Upserts presence for a user.
+    fn presence_upsert_query() -> Query<Self>;
+    /// Loads presence for a user.
+    fn presence_load_query() -> Query<Self>;
+    /// Upserts a room member's membership event.
+    fn member_upsert_query() -> Query<Self>;
+    /// Upserts a room member's profile.
+    fn member_profile_upsert_query() -> Query<Self>;
+    /// Upserts a room state event.
+    fn state_upsert_query() -> Query<Self>;
+    /// Loads a single room state event by type and state key.
+    fn state_load_query() -> Query<Self>;
+    /// Upserts a read receipt.
+    fn receipt_upsert_query() -> Query<Self>;
+    /// Upserts room info, keyed by whether it is stripped (invite) state.
+    fn room_upsert_query() -> Query<Self>;
+    /// Loads joined room info for a single room.
+    fn room_info_load_query() -> Query<Self>;
+    /// Loads every state event of a given type in a room.
+    fn state_events_load_query() -> Query<Self>;
+    /// Loads every stored member profile in a room.
+    fn profiles_load_query() -> Query<Self>;
+    /// Loads the ids of every member of a room.
+    fn user_ids_load_query() -> Query<Self>;
+    /// Loads info for every joined room.
+    fn room_infos_load_query() -> Query<Self>;
+    /// Loads info for every invited (stripped) room.
+    fn stripped_room_infos_load_query() -> Query<Self>;
+    /// Loads a single user's receipt of a given type in a room.
+    fn user_receipt_load_query() -> Query<Self>;
+    /// Loads every user's receipt of a given type pointing at an event.
+    fn event_receipt_load_query() -> Query<Self>;
+    /// Loads a single stored member profile in a room.
+    fn profile_load_query() -> Query<Self>;
+    /// Loads a single room member's membership event, preferring the
+    /// non-stripped (joined/left) row over the stripped (invite) one when
+    /// both exist for the same user.
+    fn member_load_query() -> Query<Self>;
+    /// Loads the ids of every joined (non-stripped) member of a room.
+    fn joined_user_ids_load_query() -> Query<Self>;
+    /// Loads the ids of every invited (stripped) member of a room.
+    fn invited_user_ids_load_query() -> Query<Self>;
+    /// Upserts a value in the generic `kv_store` table backing the sync
+    /// token, filters, and the [`StateStore`]'s custom-value storage.
+    fn kv_upsert_query() -> Query<Self>;
+    /// Loads a value from the generic `kv_store` table.
+    fn kv_load_query() -> Query<Self>;
+
+    /// The ordered set of SQL migration scripts for this backend.
+    ///
+    /// Each entry is a single statement, applied at most once, in order; the
+    /// applied count is persisted in the `_schema_version` table by
+    /// [`StateStore::migrate`]. Like [`room_remove_queries`](Self::room_remove_queries),
+    /// these run one statement per `execute` call, so a script may not bundle
+    /// more than one command.
+    fn migrations() -> &'static [&'static str];
+    /// Creates the `_schema_version` table if it doesn't already exist.
+    fn schema_version_create_table_query() -> Query<Self>;
+    /// Seeds `_schema_version` with an initial row of `0` if it's empty.
+    fn schema_version_seed_query() -> Query<Self>;
+    /// Loads the currently applied schema version, if any has been recorded.
+    fn schema_version_load_query() -> Query<Self>;
+    /// Records the currently applied schema version.
+    fn schema_version_update_query() -> Query<Self>;
+}